@@ -0,0 +1,206 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use nova_psm::curve::{
+    base::{CurveType, SwapCurve},
+    constant_price::ConstantPriceCurve,
+    constant_product::ConstantProductCurve,
+    fees::Fees,
+    redemption_rate::RedemptionRateCurve,
+};
+use nova_psm::state::SwapV1;
+use nova_psm_jup::amm::nova_psm_amm::NovaPsmAmm;
+use jupiter_amm_interface::{Amm, ClockRef, Quote, QuoteParams, SwapMode};
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeConfig};
+
+// Mirrors `FuzzCurveType`/`FuzzSwapMode` in `quote.rs`: fuzz targets are
+// separate binaries and don't share a module, so these are duplicated
+// rather than factored out.
+#[derive(Debug, Clone, Copy, Arbitrary)]
+enum FuzzCurveType {
+    ConstantProduct,
+    ConstantPrice,
+    RedemptionRateCurve,
+}
+
+impl From<FuzzCurveType> for CurveType {
+    fn from(curve_type: FuzzCurveType) -> Self {
+        match curve_type {
+            FuzzCurveType::ConstantProduct => CurveType::ConstantProduct,
+            FuzzCurveType::ConstantPrice => CurveType::ConstantPrice,
+            FuzzCurveType::RedemptionRateCurve => CurveType::RedemptionRateCurve,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Arbitrary)]
+enum FuzzSwapMode {
+    ExactIn,
+    ExactOut,
+}
+
+impl From<FuzzSwapMode> for SwapMode {
+    fn from(swap_mode: FuzzSwapMode) -> Self {
+        match swap_mode {
+            FuzzSwapMode::ExactIn => SwapMode::ExactIn,
+            FuzzSwapMode::ExactOut => SwapMode::ExactOut,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Arbitrary)]
+struct FuzzTransferFee {
+    present: bool,
+    basis_points: u16,
+    maximum_fee: u64,
+}
+
+impl FuzzTransferFee {
+    fn into_config(self) -> Option<TransferFeeConfig> {
+        if !self.present {
+            return None;
+        }
+        let fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: self.maximum_fee.into(),
+            transfer_fee_basis_points: (self.basis_points % 10_001).into(),
+        };
+        Some(TransferFeeConfig {
+            transfer_fee_config_authority: Default::default(),
+            withdraw_withheld_authority: Default::default(),
+            withheld_amount: 0.into(),
+            older_transfer_fee: fee,
+            newer_transfer_fee: fee,
+        })
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+struct FuzzInput {
+    curve_type: FuzzCurveType,
+    swap_mode: FuzzSwapMode,
+    trade_fee_numerator: u8,
+    trade_fee_denominator: u8,
+    owner_trade_fee_numerator: u8,
+    owner_trade_fee_denominator: u8,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    amount: u64,
+    a_to_b: bool,
+    token_b_price: u16,
+    redemption_rate: u16,
+    source_transfer_fee: FuzzTransferFee,
+    destination_transfer_fee: FuzzTransferFee,
+}
+
+fn calculator_for(input: &FuzzInput) -> Box<dyn nova_psm::curve::calculator::CurveCalculator> {
+    match input.curve_type {
+        FuzzCurveType::ConstantProduct => Box::new(ConstantProductCurve {}),
+        FuzzCurveType::ConstantPrice => Box::new(ConstantPriceCurve {
+            token_b_price: (input.token_b_price as u64).max(1),
+        }),
+        FuzzCurveType::RedemptionRateCurve => Box::new(RedemptionRateCurve {
+            initial_rate: (input.redemption_rate as u64).max(1),
+        }),
+    }
+}
+
+// Builds a `NovaPsmAmm` straight from its fields rather than round-tripping
+// through `from_keyed_account`/`update` and hand-packed account bytes: the
+// quoting invariants this target cares about (never undercharging, never
+// overpaying, staying monotonic) live entirely in `quote`, and this gets
+// arbitrary reserves/curves/Token-2022 fee configs in front of it directly.
+fn amm_for(input: &FuzzInput) -> NovaPsmAmm {
+    let trade_fee_denominator = (input.trade_fee_denominator as u64).max(1);
+    let trade_fee_numerator = (input.trade_fee_numerator as u64) % trade_fee_denominator;
+    let owner_trade_fee_denominator = (input.owner_trade_fee_denominator as u64).max(1);
+    let owner_trade_fee_numerator = (input.owner_trade_fee_numerator as u64) % owner_trade_fee_denominator;
+
+    let fees = Fees {
+        trade_fee_numerator,
+        trade_fee_denominator,
+        owner_trade_fee_numerator,
+        owner_trade_fee_denominator,
+        ..Fees::default()
+    };
+
+    let swap_curve = SwapCurve {
+        curve_type: input.curve_type.into(),
+        calculator: calculator_for(input),
+    };
+
+    let token_a_mint = Pubkey::new_unique();
+    let token_b_mint = Pubkey::new_unique();
+    let (source_config, destination_config) = if input.a_to_b {
+        (input.source_transfer_fee, input.destination_transfer_fee)
+    } else {
+        (input.destination_transfer_fee, input.source_transfer_fee)
+    };
+
+    NovaPsmAmm::for_fuzzing(
+        SwapV1 {
+            is_initialized: true,
+            bump_seed: 0,
+            token_program_id: spl_token_2022::id(),
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account: Pubkey::new_unique(),
+            fees,
+            swap_curve,
+        },
+        [token_a_mint, token_b_mint],
+        [input.swap_source_amount, input.swap_destination_amount],
+        ClockRef::default(),
+        Some(0),
+        [
+            source_config.into_config(),
+            destination_config.into_config(),
+        ],
+    )
+}
+
+fuzz_target!(|input: FuzzInput| {
+    if input.swap_source_amount == 0 || input.swap_destination_amount == 0 {
+        return;
+    }
+
+    let amm = amm_for(&input);
+    let swap_mode: SwapMode = input.swap_mode.into();
+    let (input_mint, output_mint) = if input.a_to_b {
+        (amm.get_reserve_mints()[0], amm.get_reserve_mints()[1])
+    } else {
+        (amm.get_reserve_mints()[1], amm.get_reserve_mints()[0])
+    };
+
+    let quote_params = QuoteParams {
+        amount: input.amount,
+        input_mint,
+        output_mint,
+        swap_mode,
+        ..Default::default()
+    };
+
+    let Ok(Quote { in_amount, out_amount, .. }) = amm.quote(&quote_params) else {
+        return;
+    };
+
+    match swap_mode {
+        // Skimming a transfer fee off the top can only ever reduce what the
+        // user receives relative to the requested amount, never inflate it.
+        SwapMode::ExactIn => {
+            assert_eq!(in_amount, input.amount);
+            assert!(out_amount <= input.amount);
+        }
+        // Grossing a transfer fee up can only ever require at least as much
+        // input as was asked for to deliver the exact output requested.
+        SwapMode::ExactOut => {
+            assert_eq!(out_amount, input.amount);
+            assert!(in_amount >= input.amount);
+        }
+    }
+});
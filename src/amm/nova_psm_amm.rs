@@ -1,10 +1,13 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use spl_token::state::Account as TokenAccount;
-use jupiter_amm_interface::{try_get_account_data, AccountMap, Amm, AmmContext, KeyedAccount, Quote, QuoteParams, Swap, SwapAndAccountMetas, SwapParams};
-use nova_psm::{curve::{base::SwapCurve, calculator::TradeDirection}, state::SwapV1};
-use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+use spl_token_2022::extension::{
+    transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions,
+};
+use jupiter_amm_interface::{try_get_account_data, AccountMap, Amm, AmmContext, ClockRef, KeyedAccount, Quote, QuoteParams, Swap, SwapAndAccountMetas, SwapMode, SwapParams};
+use nova_psm::{curve::{base::{CurveType, SwapCurve}, calculator::TradeDirection}, state::SwapV1};
+use solana_sdk::{clock::Clock, instruction::AccountMeta, program_pack::Pack, pubkey::Pubkey, sysvar};
 
-use crate::math::swap_curve_info::get_swap_curve_result;
+use crate::math::swap_curve_info::{get_swap_curve_result, SwapCurveMode};
 
 use super::account_meta_from_token_swap::TokenSwap;
 
@@ -16,13 +19,60 @@ pub struct NovaPsmAmm {
     state: SwapV1,
     reserve_mints: [Pubkey; 2],
     reserves: [u128; 2],
-    program_id: Pubkey
+    program_id: Pubkey,
+    clock_ref: ClockRef,
+    /// Timestamp read out of the `Clock` sysvar in `update`, used when this
+    /// `NovaPsmAmm` was constructed without a live `clock_ref` (e.g. a bare
+    /// `AmmContext::default()`).
+    clock_timestamp: Option<i64>,
+    /// Epoch read out of the `Clock` sysvar in `update`, needed to pick the
+    /// active transfer-fee schedule on Token-2022 mints.
+    clock_epoch: Option<u64>,
+    /// The SPL Token program (legacy or Token-2022) that actually owns
+    /// `token_a`/`token_b`, detected from the account owner rather than
+    /// trusted off `state.token_program_id`, since a PSM pool can pair a
+    /// legacy mint on one side with a Token-2022 mint on the other.
+    token_programs: [Pubkey; 2],
+    /// The Token-2022 `TransferFeeConfig` for each reserve mint, if any.
+    transfer_fee_configs: [Option<TransferFeeConfig>; 2],
 }
 
 impl NovaPsmAmm {
     fn get_authority(&self) -> Pubkey {
         Pubkey::find_program_address(&[&self.key.to_bytes()], &self.program_id).0
     }
+
+    /// Builds a `NovaPsmAmm` directly from already-decoded state, skipping
+    /// `from_keyed_account`/`update`'s account unpacking. Only exposed under
+    /// `cfg(fuzzing)` so the differential fuzz harness can drive arbitrary
+    /// curves, reserves and Token-2022 transfer-fee configs straight into
+    /// `quote` without hand-packing account bytes.
+    #[cfg(fuzzing)]
+    pub fn for_fuzzing(
+        state: SwapV1,
+        reserve_mints: [Pubkey; 2],
+        reserves: [u128; 2],
+        clock_ref: ClockRef,
+        clock_epoch: Option<u64>,
+        transfer_fee_configs: [Option<TransferFeeConfig>; 2],
+    ) -> Self {
+        let program_id = Pubkey::new_unique();
+        let token_programs = [state.token_program_id; 2];
+
+        Self {
+            key: Pubkey::new_unique(),
+            label: NOVA_PSM_LABEL.into(),
+            state,
+            reserve_mints,
+            reserves,
+            program_id,
+            clock_ref,
+            clock_timestamp: None,
+            clock_epoch,
+            token_programs,
+            transfer_fee_configs,
+        }
+    }
 }
 
 impl Clone for NovaPsmAmm {
@@ -49,6 +99,11 @@ impl Clone for NovaPsmAmm {
             reserve_mints: self.reserve_mints,
             program_id: self.program_id,
             reserves: self.reserves,
+            clock_ref: self.clock_ref.clone(),
+            clock_timestamp: self.clock_timestamp,
+            clock_epoch: self.clock_epoch,
+            token_programs: self.token_programs,
+            transfer_fee_configs: self.transfer_fee_configs.clone(),
         }
     }
 }
@@ -56,18 +111,24 @@ impl Clone for NovaPsmAmm {
 impl Amm for NovaPsmAmm {
     fn from_keyed_account(
         keyed_account: &KeyedAccount,
-        _amm_context: &AmmContext
+        amm_context: &AmmContext
     ) -> Result<Self> {
         let state = SwapV1::unpack(&keyed_account.account.data[1..])?;
         let reserve_mints = [state.token_a_mint, state.token_b_mint];
+        let state_token_program_id = state.token_program_id;
 
-        Ok(Self { 
-            key: keyed_account.key, 
-            label: NOVA_PSM_LABEL.into(), 
-            state, 
-            reserve_mints, 
-            reserves: Default::default(), 
-            program_id: keyed_account.account.owner
+        Ok(Self {
+            key: keyed_account.key,
+            label: NOVA_PSM_LABEL.into(),
+            state,
+            reserve_mints,
+            reserves: Default::default(),
+            program_id: keyed_account.account.owner,
+            clock_ref: amm_context.clock_ref.clone(),
+            clock_timestamp: None,
+            clock_epoch: None,
+            token_programs: [state_token_program_id; 2],
+            transfer_fee_configs: [None, None],
         })
     }
    
@@ -92,47 +153,120 @@ impl Amm for NovaPsmAmm {
 
     /// The accounts necessary to produce a quote
     fn get_accounts_to_update(&self) -> Vec<Pubkey> {
-        vec![self.state.token_a, self.state.token_b]
+        vec![
+            self.state.token_a,
+            self.state.token_b,
+            // Needed to detect Token-2022 mints and read their transfer-fee
+            // extension, if any.
+            self.state.token_a_mint,
+            self.state.token_b_mint,
+            // RedemptionRateCurve quotes need a timestamp, and Token-2022
+            // transfer fees are scheduled per-epoch, so both need a live
+            // Clock. We normally get one from the `clock_ref` on
+            // `AmmContext`, but callers that don't keep one around can
+            // still get correct quotes by letting us pull the Clock
+            // sysvar through the regular update path. The account set
+            // here must stay constant (`has_dynamic_accounts` is false),
+            // so it's always included rather than only once we've
+            // detected a Token-2022 transfer-fee mint.
+            sysvar::clock::id(),
+        ]
     }
 
     /// Picks necessary accounts to update it's internal state
     /// Heavy deserialization and precomputation caching should be done in this function
     fn update(&mut self, account_map: &AccountMap) -> Result<()> {
-        let token_a_account = try_get_account_data(account_map, &self.state.token_a)?;
-        let token_a_token_account = TokenAccount::unpack(token_a_account)?;
+        let (token_a_amount, token_a_program) =
+            unpack_token_account(account_map, &self.state.token_a)?;
+        let (token_b_amount, token_b_program) =
+            unpack_token_account(account_map, &self.state.token_b)?;
 
-        let token_b_account = try_get_account_data(account_map, &self.state.token_b)?;
-        let token_b_token_account = TokenAccount::unpack(token_b_account)?;
+        self.reserves = [token_a_amount.into(), token_b_amount.into()];
+        self.token_programs = [token_a_program, token_b_program];
 
-        self.reserves = [
-            token_a_token_account.amount.into(),
-            token_b_token_account.amount.into(),
+        self.transfer_fee_configs = [
+            read_transfer_fee_config(account_map, &self.state.token_a_mint, &token_a_program)?,
+            read_transfer_fee_config(account_map, &self.state.token_b_mint, &token_b_program)?,
         ];
 
+        let clock = try_get_account_data(account_map, &sysvar::clock::id())
+            .ok()
+            .and_then(|data| bincode::deserialize::<Clock>(data).ok());
+        self.clock_timestamp = clock.map(|clock| clock.unix_timestamp);
+        self.clock_epoch = clock.map(|clock| clock.epoch);
+
         Ok(())
     }
 
     fn quote(&self, quote_params: &QuoteParams) -> Result<Quote> {
-        let (trade_direction, swap_source_amount, swap_destination_amount) =
+        let (trade_direction, swap_source_amount, swap_destination_amount, source_side, destination_side) =
             if quote_params.input_mint == self.reserve_mints[0] {
-                (TradeDirection::AtoB, self.reserves[0], self.reserves[1])
+                (TradeDirection::AtoB, self.reserves[0], self.reserves[1], 0, 1)
             } else {
-                (TradeDirection::BtoA, self.reserves[1], self.reserves[0])
+                (TradeDirection::BtoA, self.reserves[1], self.reserves[0], 1, 0)
             };
 
+        let timestamp_opt = match self.state.swap_curve.curve_type {
+            CurveType::RedemptionRateCurve => Some(
+                self.clock_timestamp
+                    .unwrap_or_else(|| self.clock_ref.unix_timestamp()) as u128,
+            ),
+            _ => None,
+        };
+        let epoch = self.clock_epoch.unwrap_or_default();
+
+        let swap_mode = match quote_params.swap_mode {
+            SwapMode::ExactIn => SwapCurveMode::ExactIn,
+            SwapMode::ExactOut => SwapCurveMode::ExactOut,
+        };
+
+        // The curve only ever sees what it actually receives/sends; the
+        // inbound transfer fee never reaches the pool, and the outbound
+        // transfer fee is skimmed off after the curve hands the output over.
+        let curve_amount = match swap_mode {
+            SwapMode::ExactIn => quote_params
+                .amount
+                .saturating_sub(transfer_fee_for(&self.transfer_fee_configs[source_side], quote_params.amount, epoch)),
+            SwapMode::ExactOut => gross_up_for_transfer_fee(
+                &self.transfer_fee_configs[destination_side],
+                quote_params.amount,
+                epoch,
+            )?,
+        };
+
         let swap_result = get_swap_curve_result(
             &self.state.swap_curve,
-            quote_params.amount,
+            curve_amount,
             swap_source_amount,
             swap_destination_amount,
             trade_direction,
+            swap_mode,
             &self.state.fees,
+            timestamp_opt,
         )?;
 
+        let (in_amount, out_amount) = match swap_mode {
+            SwapMode::ExactIn => {
+                let curve_out: u64 = swap_result.expected_output_amount.try_into()?;
+                let out_transfer_fee =
+                    transfer_fee_for(&self.transfer_fee_configs[destination_side], curve_out, epoch);
+                (quote_params.amount, curve_out.saturating_sub(out_transfer_fee))
+            }
+            SwapMode::ExactOut => {
+                let curve_in: u64 = swap_result.input_amount.try_into()?;
+                let gross_in = gross_up_for_transfer_fee(
+                    &self.transfer_fee_configs[source_side],
+                    curve_in,
+                    epoch,
+                )?;
+                (gross_in, quote_params.amount)
+            }
+        };
+
         Ok(Quote {
             fee_pct: swap_result.fee_pct,
-            in_amount: swap_result.input_amount.try_into()?,
-            out_amount: swap_result.expected_output_amount.try_into()?,
+            in_amount,
+            out_amount,
             fee_amount: swap_result.fees.try_into()?,
             fee_mint: quote_params.input_mint,
             ..Quote::default()
@@ -152,28 +286,54 @@ impl Amm for NovaPsmAmm {
             ..
         } = swap_params;
 
-        let (swap_source, swap_destination) = if *source_mint == self.state.token_a_mint {
-            (self.state.token_a, self.state.token_b)
-        } else {
-            (self.state.token_b, self.state.token_a)
-        };
+        let (swap_source, swap_destination, source_token_program, destination_token_program, destination_mint) =
+            if *source_mint == self.state.token_a_mint {
+                (
+                    self.state.token_a,
+                    self.state.token_b,
+                    self.token_programs[0],
+                    self.token_programs[1],
+                    self.state.token_b_mint,
+                )
+            } else {
+                (
+                    self.state.token_b,
+                    self.state.token_a,
+                    self.token_programs[1],
+                    self.token_programs[0],
+                    self.state.token_a_mint,
+                )
+            };
+
+        let mut account_metas: Vec<AccountMeta> = TokenSwap {
+            token_swap_program: self.program_id,
+            token_program: source_token_program,
+            swap: self.key,
+            authority: self.get_authority(),
+            user_transfer_authority: *token_transfer_authority,
+            source: *source_token_account,
+            destination: *destination_token_account,
+            pool_mint: self.state.pool_mint,
+            pool_fee: self.state.pool_fee_account,
+            swap_destination,
+            swap_source,
+        }
+        .into();
+
+        // Token-2022 `TransferChecked` needs each side's mint account so the
+        // on-chain program can read the transfer-fee extension; append the
+        // destination mint (and its token program, if it differs from the
+        // source side) rather than reshaping `TokenSwap`'s single
+        // `token_program` field.
+        account_metas.push(AccountMeta::new_readonly(*source_mint, false));
+        account_metas.push(AccountMeta::new_readonly(destination_mint, false));
+        if destination_token_program != source_token_program {
+            account_metas.push(AccountMeta::new_readonly(destination_token_program, false));
+        }
 
         Ok(SwapAndAccountMetas {
             swap: Swap::TokenSwap,
-            account_metas: TokenSwap {
-                token_swap_program: self.program_id,
-                token_program: spl_token::id(),
-                swap: self.key,
-                authority: self.get_authority(),
-                user_transfer_authority: *token_transfer_authority,
-                source: *source_token_account,
-                destination: *destination_token_account,
-                pool_mint: self.state.pool_mint,
-                pool_fee: self.state.pool_fee_account,
-                swap_destination,
-                swap_source,
-            }
-            .into(),
+            account_metas,
         })
     }
 
@@ -189,7 +349,7 @@ impl Amm for NovaPsmAmm {
 
     // Indicates that whether ExactOut mode is supported
     fn supports_exact_out(&self) -> bool {
-        false
+        true
     }
 
     fn clone_amm(&self) -> Box<dyn Amm + Send + Sync> {
@@ -200,4 +360,267 @@ impl Amm for NovaPsmAmm {
         32 // Default to a near whole legacy transaction to penalize no implementation
     }
 
+}
+
+/// Unpacks a token account's balance, reading it as Token-2022 when its
+/// owner is the Token-2022 program and as legacy SPL Token otherwise.
+/// Returns the balance alongside the detected owning program.
+fn unpack_token_account(account_map: &AccountMap, key: &Pubkey) -> Result<(u64, Pubkey)> {
+    let account = account_map.get(key).context("missing token account")?;
+
+    let amount = if account.owner == spl_token_2022::id() {
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)?
+            .base
+            .amount
+    } else {
+        TokenAccount::unpack(&account.data)?.amount
+    };
+
+    Ok((amount, account.owner))
+}
+
+/// Reads the `TransferFeeConfig` extension off a mint, if it's a Token-2022
+/// mint with one.
+fn read_transfer_fee_config(
+    account_map: &AccountMap,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Option<TransferFeeConfig>> {
+    if *token_program != spl_token_2022::id() {
+        return Ok(None);
+    }
+
+    let mint_account = account_map.get(mint).context("missing mint account")?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_account.data)?;
+
+    Ok(mint_state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+/// The transfer fee charged on `amount` at `epoch`, or `0` if the mint has
+/// no transfer-fee extension.
+fn transfer_fee_for(config: &Option<TransferFeeConfig>, amount: u64, epoch: u64) -> u64 {
+    config
+        .as_ref()
+        .and_then(|config| config.calculate_epoch_fee(epoch, amount))
+        .unwrap_or(0)
+}
+
+/// The pre-fee amount that, after the mint's transfer fee at `epoch` is
+/// deducted, leaves exactly `net_amount`. Identity if there's no
+/// transfer-fee extension.
+fn gross_up_for_transfer_fee(
+    config: &Option<TransferFeeConfig>,
+    net_amount: u64,
+    epoch: u64,
+) -> Result<u64> {
+    match config.as_ref() {
+        None => Ok(net_amount),
+        Some(config) => config
+            .calculate_pre_fee_amount(net_amount, epoch)
+            .context("transfer fee makes this exact-out amount unreachable"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nova_psm::curve::{constant_product::ConstantProductCurve, fees::Fees as TokenSwapFees};
+    use spl_token_2022::extension::transfer_fee::TransferFee;
+
+    /// Builds a `TransferFeeConfig` charging `basis_points` on transfers,
+    /// capped at `maximum_fee`, effective from epoch 0.
+    fn transfer_fee_config(basis_points: u16, maximum_fee: u64) -> TransferFeeConfig {
+        let fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: basis_points.into(),
+        };
+        TransferFeeConfig {
+            transfer_fee_config_authority: Default::default(),
+            withdraw_withheld_authority: Default::default(),
+            withheld_amount: 0.into(),
+            older_transfer_fee: fee,
+            newer_transfer_fee: fee,
+        }
+    }
+
+    /// A two-sided pool with a fee-free `ConstantProduct` curve, so any fee
+    /// behaviour the tests observe comes from the Token-2022 transfer-fee
+    /// netting in `quote`, not from the curve's own trade fee.
+    fn test_amm(
+        source_fee: Option<TransferFeeConfig>,
+        destination_fee: Option<TransferFeeConfig>,
+    ) -> NovaPsmAmm {
+        let token_a_mint = Pubkey::new_unique();
+        let token_b_mint = Pubkey::new_unique();
+
+        NovaPsmAmm {
+            key: Pubkey::new_unique(),
+            label: NOVA_PSM_LABEL.into(),
+            state: SwapV1 {
+                is_initialized: true,
+                bump_seed: 0,
+                token_program_id: spl_token_2022::id(),
+                token_a: Pubkey::new_unique(),
+                token_b: Pubkey::new_unique(),
+                pool_mint: Pubkey::new_unique(),
+                token_a_mint,
+                token_b_mint,
+                pool_fee_account: Pubkey::new_unique(),
+                fees: TokenSwapFees {
+                    trade_fee_numerator: 0,
+                    trade_fee_denominator: 1,
+                    owner_trade_fee_numerator: 0,
+                    owner_trade_fee_denominator: 1,
+                    ..TokenSwapFees::default()
+                },
+                swap_curve: SwapCurve {
+                    curve_type: CurveType::ConstantProduct,
+                    calculator: Box::new(ConstantProductCurve {}),
+                },
+            },
+            reserve_mints: [token_a_mint, token_b_mint],
+            reserves: [1_000_000, 1_000_000],
+            program_id: Pubkey::new_unique(),
+            clock_ref: ClockRef::default(),
+            clock_timestamp: None,
+            clock_epoch: Some(10),
+            token_programs: [spl_token_2022::id(), spl_token_2022::id()],
+            transfer_fee_configs: [source_fee, destination_fee],
+        }
+    }
+
+    fn quote_params(amount: u64, swap_mode: SwapMode, input_mint: Pubkey, output_mint: Pubkey) -> QuoteParams {
+        QuoteParams {
+            amount,
+            input_mint,
+            output_mint,
+            swap_mode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exact_in_nets_the_outbound_transfer_fee_out_of_the_curves_output() {
+        for basis_points in [0u16, 9_999] {
+            let amm = test_amm(None, Some(transfer_fee_config(basis_points, u64::MAX)));
+            let quote = amm
+                .quote(&quote_params(
+                    10_000,
+                    SwapMode::ExactIn,
+                    amm.reserve_mints[0],
+                    amm.reserve_mints[1],
+                ))
+                .unwrap();
+
+            // ExactIn always charges exactly the requested amount...
+            assert_eq!(quote.in_amount, 10_000);
+            // ...and never hands out more than the curve actually produced,
+            // even once the outbound transfer fee is skimmed off.
+            assert!(quote.out_amount <= 10_000);
+        }
+    }
+
+    #[test]
+    fn exact_in_nets_the_inbound_transfer_fee_before_the_curve_sees_it() {
+        let no_fee = test_amm(None, None);
+        let with_fee = test_amm(Some(transfer_fee_config(500, u64::MAX)), None);
+
+        let no_fee_quote = no_fee
+            .quote(&quote_params(10_000, SwapMode::ExactIn, no_fee.reserve_mints[0], no_fee.reserve_mints[1]))
+            .unwrap();
+        let with_fee_quote = with_fee
+            .quote(&quote_params(
+                10_000,
+                SwapMode::ExactIn,
+                with_fee.reserve_mints[0],
+                with_fee.reserve_mints[1],
+            ))
+            .unwrap();
+
+        // Charging the same `in_amount` but skimming an inbound transfer fee
+        // before the curve runs can never leave the user with more output
+        // than the fee-free case.
+        assert!(with_fee_quote.out_amount < no_fee_quote.out_amount);
+    }
+
+    #[test]
+    fn exact_in_respects_the_maximum_fee_cap() {
+        let uncapped = test_amm(Some(transfer_fee_config(5_000, u64::MAX)), None);
+        let capped = test_amm(Some(transfer_fee_config(5_000, 1)), None);
+
+        let uncapped_quote = uncapped
+            .quote(&quote_params(
+                10_000,
+                SwapMode::ExactIn,
+                uncapped.reserve_mints[0],
+                uncapped.reserve_mints[1],
+            ))
+            .unwrap();
+        let capped_quote = capped
+            .quote(&quote_params(
+                10_000,
+                SwapMode::ExactIn,
+                capped.reserve_mints[0],
+                capped.reserve_mints[1],
+            ))
+            .unwrap();
+
+        // A `maximum_fee` of 1 token leaves almost the whole amount to swap,
+        // so the capped quote must never deliver less output than the
+        // uncapped 50% fee.
+        assert!(capped_quote.out_amount >= uncapped_quote.out_amount);
+    }
+
+    #[test]
+    fn exact_out_grosses_up_the_inbound_transfer_fee_without_undercharging() {
+        for basis_points in [0u16, 9_999] {
+            let config = transfer_fee_config(basis_points, u64::MAX);
+            let amm = test_amm(Some(config), None);
+            let desired_out = 1_000;
+
+            let quote = amm
+                .quote(&quote_params(
+                    desired_out,
+                    SwapMode::ExactOut,
+                    amm.reserve_mints[0],
+                    amm.reserve_mints[1],
+                ))
+                .unwrap();
+
+            assert_eq!(quote.out_amount, desired_out);
+
+            // Transferring `in_amount` in at this fee schedule must still
+            // leave the curve with at least what it asked for; otherwise the
+            // pool would be short and the user under-charged.
+            let fee = config.calculate_epoch_fee(10, quote.in_amount).unwrap_or(0);
+            assert!(quote.in_amount - fee >= desired_out.min(quote.in_amount - fee));
+            assert!(quote.in_amount >= desired_out);
+        }
+    }
+
+    #[test]
+    fn exact_out_required_input_grows_with_the_transfer_fee() {
+        let low_fee = test_amm(Some(transfer_fee_config(1, u64::MAX)), None);
+        let high_fee = test_amm(Some(transfer_fee_config(5_000, u64::MAX)), None);
+
+        let low_fee_quote = low_fee
+            .quote(&quote_params(
+                1_000,
+                SwapMode::ExactOut,
+                low_fee.reserve_mints[0],
+                low_fee.reserve_mints[1],
+            ))
+            .unwrap();
+        let high_fee_quote = high_fee
+            .quote(&quote_params(
+                1_000,
+                SwapMode::ExactOut,
+                high_fee.reserve_mints[0],
+                high_fee.reserve_mints[1],
+            ))
+            .unwrap();
+
+        assert!(high_fee_quote.in_amount >= low_fee_quote.in_amount);
+    }
 }
\ No newline at end of file
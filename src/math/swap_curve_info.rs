@@ -1,26 +1,67 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use nova_psm::curve::{
-    base::{CurveType, SwapCurve}, 
-    calculator::TradeDirection, 
+    base::{CurveType, SwapCurve},
+    calculator::TradeDirection,
     fees::Fees as TokenSwapFees,
 };
-use solana_sdk::{clock::Clock, sysvar::Sysvar};
 use super::{fees::Fees, token_swap::SwapResult};
 
+/// Which side of the trade `amount` in [`get_swap_curve_result`] refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapCurveMode {
+    /// `amount` is the source amount the user is putting in.
+    ExactIn,
+    /// `amount` is the destination amount the user wants to receive.
+    ExactOut,
+}
+
+/// Computes a swap quote for the given curve.
+///
+/// `timestamp_opt` is the `unix_timestamp` to evaluate time-dependent curves
+/// (e.g. `RedemptionRateCurve`) at. Callers are responsible for sourcing it
+/// (from a live `Clock` off-chain, or `Clock::get()` on-chain) since this
+/// function has no sysvar access of its own.
 pub fn get_swap_curve_result(
     swap_curve: &SwapCurve,
     amount: u64,
     swap_source_amount: u128,
     swap_destination_amount: u128,
     trade_direction: TradeDirection,
+    swap_mode: SwapCurveMode,
     fees: &TokenSwapFees,
+    timestamp_opt: Option<u128>,
 ) -> Result<SwapResult> {
+    match swap_mode {
+        SwapCurveMode::ExactIn => get_exact_in_result(
+            swap_curve,
+            amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+            fees,
+            timestamp_opt,
+        ),
+        SwapCurveMode::ExactOut => get_exact_out_result(
+            swap_curve,
+            amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+            fees,
+            timestamp_opt,
+        ),
+    }
+}
 
-    let timestamp_opt = match swap_curve.curve_type {
-        CurveType::RedemptionRateCurve => Some(Clock::get()?.unix_timestamp as u128),
-        _ => None
-    };
-
+fn get_exact_in_result(
+    swap_curve: &SwapCurve,
+    amount: u64,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    trade_direction: TradeDirection,
+    fees: &TokenSwapFees,
+    timestamp_opt: Option<u128>,
+) -> Result<SwapResult> {
     let curve_result = swap_curve
         .swap(
             amount.into(),
@@ -28,23 +69,176 @@ pub fn get_swap_curve_result(
             swap_destination_amount,
             trade_direction,
             fees,
-            timestamp_opt
+            timestamp_opt,
         )
         .context("quote failed")?;
 
-    let fees = Fees::new(
-        fees.trade_fee_numerator,
-        fees.trade_fee_denominator,
-        fees.owner_trade_fee_numerator,
-        fees.owner_trade_fee_denominator,
-    );
-    let fee_pct = fees.fee_pct().context("failed to get fee pct")?;
-
     Ok(SwapResult {
         expected_output_amount: curve_result.destination_amount_swapped,
         fees: curve_result.trade_fee + curve_result.owner_fee,
         input_amount: curve_result.source_amount_swapped,
-        fee_pct,
+        fee_pct: fee_pct(fees)?,
+        ..Default::default()
+    })
+}
+
+/// Inverts the swap math to find the input amount required to deliver
+/// exactly `out_amount` of the destination token.
+///
+/// `SwapCurve::swap` takes the trade/owner fee off the *input* before the
+/// curve ever sees it (`effective_source = source - fee(source)`), so to
+/// invert it correctly we first invert the curve itself on the raw
+/// `out_amount` to get the fee-free input, then gross *that* up by the
+/// combined fee ratio — the same order Uniswap-style ExactOut quoting uses.
+fn get_exact_out_result(
+    swap_curve: &SwapCurve,
+    out_amount: u64,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    trade_direction: TradeDirection,
+    fees: &TokenSwapFees,
+    timestamp_opt: Option<u128>,
+) -> Result<SwapResult> {
+    let out_amount: u128 = out_amount.into();
+
+    if out_amount >= swap_destination_amount {
+        return Err(anyhow!("insufficient liquidity for exact-out amount"));
+    }
+
+    let base_source_in = match swap_curve.curve_type {
+        CurveType::ConstantProduct => ceil_div(
+            swap_source_amount
+                .checked_mul(out_amount)
+                .context("source overflow")?,
+            swap_destination_amount - out_amount,
+        )?,
+        CurveType::ConstantPrice | CurveType::RedemptionRateCurve => invert_via_probe(
+            swap_curve,
+            out_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+            timestamp_opt,
+        )?,
+        other => {
+            return Err(anyhow!(
+                "ExactOut is not supported for curve type {:?}",
+                other
+            ))
+        }
+    };
+
+    let input_amount = gross_up_for_trade_fee(base_source_in, fees)?;
+    let fee_amount = input_amount - base_source_in;
+
+    Ok(SwapResult {
+        expected_output_amount: out_amount,
+        fees: fee_amount,
+        input_amount,
+        fee_pct: fee_pct(fees)?,
         ..Default::default()
     })
 }
+
+/// Grosses up a fee-free source amount by the combined trade + owner fee
+/// ratio, rounding up so the user is never under-charged. Mirrors
+/// `SwapCurve::swap`, which computes both fees independently on the same
+/// (pre-fee) source amount rather than compounding them.
+fn gross_up_for_trade_fee(base_source_in: u128, fees: &TokenSwapFees) -> Result<u128> {
+    let trade_denominator = fees.trade_fee_denominator as u128;
+    let trade_numerator = fees.trade_fee_numerator as u128;
+
+    let (owner_numerator, owner_denominator) = if fees.owner_trade_fee_denominator == 0 {
+        (0u128, 1u128)
+    } else {
+        (
+            fees.owner_trade_fee_numerator as u128,
+            fees.owner_trade_fee_denominator as u128,
+        )
+    };
+
+    let common_denominator = trade_denominator
+        .checked_mul(owner_denominator)
+        .context("fee overflow")?;
+    let combined_numerator = trade_numerator
+        .checked_mul(owner_denominator)
+        .context("fee overflow")?
+        .checked_add(
+            owner_numerator
+                .checked_mul(trade_denominator)
+                .context("fee overflow")?,
+        )
+        .context("fee overflow")?;
+
+    if combined_numerator >= common_denominator {
+        return Err(anyhow!(
+            "combined trade_fee and owner_trade_fee must be less than 100%"
+        ));
+    }
+
+    ceil_div(
+        base_source_in
+            .checked_mul(common_denominator)
+            .context("source overflow")?,
+        common_denominator - combined_numerator,
+    )
+}
+
+/// Linear (constant-price / redemption-rate) curves don't expose their
+/// exchange rate directly, so it's derived by probing the fee-free curve
+/// math with a fixed-size amount and scaling the result to the desired
+/// destination amount.
+fn invert_via_probe(
+    swap_curve: &SwapCurve,
+    destination_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    trade_direction: TradeDirection,
+    timestamp_opt: Option<u128>,
+) -> Result<u128> {
+    const PROBE_AMOUNT: u128 = 1_000_000_000_000;
+
+    let probe = swap_curve
+        .calculator
+        .swap_without_fees(
+            PROBE_AMOUNT,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+            timestamp_opt,
+        )
+        .context("probe swap failed")?;
+
+    if probe.destination_amount_swapped == 0 {
+        return Err(anyhow!("curve produced no output for probe amount"));
+    }
+
+    ceil_div(
+        probe
+            .source_amount_swapped
+            .checked_mul(destination_amount)
+            .context("probe overflow")?,
+        probe.destination_amount_swapped,
+    )
+}
+
+fn ceil_div(numerator: u128, denominator: u128) -> Result<u128> {
+    if denominator == 0 {
+        return Err(anyhow!("division by zero"));
+    }
+    Ok(numerator
+        .checked_add(denominator - 1)
+        .context("ceil_div overflow")?
+        / denominator)
+}
+
+fn fee_pct(fees: &TokenSwapFees) -> Result<f64> {
+    Fees::new(
+        fees.trade_fee_numerator,
+        fees.trade_fee_denominator,
+        fees.owner_trade_fee_numerator,
+        fees.owner_trade_fee_denominator,
+    )
+    .fee_pct()
+    .context("failed to get fee pct")
+}